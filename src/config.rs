@@ -0,0 +1,98 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mpris::Metadata;
+use serde::Deserialize;
+
+/// Rules controlling which MPRIS players count toward idle inhibition.
+///
+/// Loaded from a small TOML file so a player that's merely playing
+/// background music doesn't have to keep the screen awake, while a video
+/// player does. Reloaded on `SIGHUP` rather than re-read on every poll, so
+/// editing the file doesn't race the event loop.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Player `identity()` values allowed to inhibit idling. Empty means
+    /// every identity is allowed unless listed in `blocked_identities`.
+    #[serde(default)]
+    pub allowed_identities: Vec<String>,
+    /// Player `identity()` values never allowed to inhibit idling, checked
+    /// before `allowed_identities`.
+    #[serde(default)]
+    pub blocked_identities: Vec<String>,
+    /// Only count a player as inhibiting when its current track's metadata
+    /// looks like video rather than audio.
+    #[serde(default)]
+    pub require_video: bool,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/wl-mpris-idle-inhibit/config.toml`, falling back to
+    /// `~/.config/...` when `XDG_CONFIG_HOME` is unset.
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("wl-mpris-idle-inhibit")
+            .join("config.toml")
+    }
+
+    /// Loads the config from `path`. A missing file is treated as the
+    /// permissive default rather than an error, so running without any
+    /// config at all is the common case.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).map_err(ConfigError::Parse),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(ConfigError::Read(e)),
+        }
+    }
+
+    /// Whether a player with the given `identity` should be considered at
+    /// all, irrespective of its playback status or metadata.
+    pub fn allows_identity(&self, identity: &str) -> bool {
+        if self.blocked_identities.iter().any(|b| b == identity) {
+            return false;
+        }
+        self.allowed_identities.is_empty()
+            || self.allowed_identities.iter().any(|a| a == identity)
+    }
+
+    /// Whether `metadata` satisfies `require_video`. Mirrors the way
+    /// empress inspects player properties: a track counts as video when it
+    /// has a `mpris:length` and either an `xesam:mimetype` starting with
+    /// `video/` or an `xesam:videoCodec` field. A track with neither field
+    /// counts as audio.
+    pub fn allows_metadata(&self, metadata: &Metadata) -> bool {
+        if !self.require_video {
+            return true;
+        }
+        metadata.length_in_microseconds().is_some() && has_video_indicator(metadata)
+    }
+}
+
+fn has_video_indicator(metadata: &Metadata) -> bool {
+    let mimetype = metadata
+        .get("xesam:mimetype")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    mimetype.starts_with("video/") || metadata.get("xesam:videoCodec").is_some()
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(e) => write!(f, "could not read config file: {}", e),
+            ConfigError::Parse(e) => write!(f, "could not parse config file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,119 @@
+use std::sync::{Arc, Mutex};
+
+use log::warn;
+use zbus::blocking::{Connection, ConnectionBuilder};
+use zbus::{dbus_interface, fdo};
+
+/// Well-known name and object path this daemon exposes, following
+/// empress's model of being a D-Bus-addressable MPRIS tool rather than a
+/// silent background process.
+pub const SERVICE_NAME: &str = "org.wl_mpris_idle_inhibit.Daemon";
+pub const OBJECT_PATH: &str = "/org/wl_mpris_idle_inhibit/Daemon";
+const INTERFACE_NAME: &str = "org.wl_mpris_idle_inhibit.Daemon1";
+
+/// Manual override set via the `Inhibit`/`Release`/`SetMode` D-Bus methods,
+/// consulted by the event loop alongside the usual playback-derived
+/// decision so a keybind or status bar widget can force-hold or
+/// force-release idle inhibition regardless of playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Auto,
+    ForceOn,
+    ForceOff,
+}
+
+/// State shared between the D-Bus interface, which serves method calls on
+/// its own connection thread, and the calloop event loop, which applies the
+/// resulting decision and keeps `active` in sync with reality.
+#[derive(Default)]
+pub struct Shared {
+    pub mode: Mutex<Mode>,
+    pub active: Mutex<bool>,
+    /// Wakes the calloop event loop as soon as `mode` changes, so a manual
+    /// override takes effect immediately instead of waiting for the next
+    /// player poll or event. Set once `run_session` has built its event
+    /// loop; `None` only in the brief window before that.
+    pub override_ping: Mutex<Option<calloop::ping::Ping>>,
+}
+
+impl Shared {
+    /// Wakes the event loop if it has registered a ping source, so it
+    /// re-evaluates the inhibit decision right away.
+    fn notify_override_changed(&self) {
+        if let Some(ping) = self.override_ping.lock().unwrap().as_ref() {
+            ping.ping();
+        }
+    }
+}
+
+struct DaemonInterface {
+    shared: Arc<Shared>,
+}
+
+#[dbus_interface(name = "org.wl_mpris_idle_inhibit.Daemon1")]
+impl DaemonInterface {
+    /// Force idle inhibition on, regardless of playback.
+    fn inhibit(&self) {
+        *self.shared.mode.lock().unwrap() = Mode::ForceOn;
+        self.shared.notify_override_changed();
+    }
+
+    /// Drop any override, going back to deciding from playback state alone.
+    fn release(&self) {
+        *self.shared.mode.lock().unwrap() = Mode::Auto;
+        self.shared.notify_override_changed();
+    }
+
+    /// Sets the override mode directly; `mode` must be one of `auto`,
+    /// `force-on`, or `force-off`.
+    fn set_mode(&self, mode: &str) -> fdo::Result<()> {
+        let parsed = match mode {
+            "auto" => Mode::Auto,
+            "force-on" => Mode::ForceOn,
+            "force-off" => Mode::ForceOff,
+            other => {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "unknown mode {other:?}, expected auto, force-on, or force-off"
+                )))
+            }
+        };
+        *self.shared.mode.lock().unwrap() = parsed;
+        self.shared.notify_override_changed();
+        Ok(())
+    }
+
+    /// Whether idling is currently being inhibited.
+    #[dbus_interface(property)]
+    fn active(&self) -> bool {
+        *self.shared.active.lock().unwrap()
+    }
+}
+
+/// Registers `SERVICE_NAME` on the session bus and serves `DaemonInterface`
+/// at `OBJECT_PATH` on a connection owned by the caller. The returned
+/// `Connection` is cheap to clone and is kept around so the event loop can
+/// use it to emit `InhibitorChanged` whenever the inhibitor is created or
+/// destroyed.
+pub fn spawn(shared: Arc<Shared>) -> zbus::Result<Connection> {
+    ConnectionBuilder::session()?
+        .name(SERVICE_NAME)?
+        .serve_at(OBJECT_PATH, DaemonInterface { shared })?
+        .build()
+}
+
+/// Emits the `InhibitorChanged` signal and updates the `Active` property to
+/// match. Called by the event loop right after it creates or destroys the
+/// idle inhibitor.
+pub fn notify_active_changed(connection: &Connection, shared: &Shared, active: bool) {
+    *shared.active.lock().unwrap() = active;
+    if let Err(e) = connection.emit_signal(
+        None::<&str>,
+        OBJECT_PATH,
+        INTERFACE_NAME,
+        "InhibitorChanged",
+        &(active,),
+    ) {
+        warn!("could not emit InhibitorChanged signal: {}", e);
+    }
+}
@@ -1,9 +1,24 @@
+mod config;
+mod dbus_server;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
+use calloop::channel;
+use calloop::timer::{TimeoutAction, Timer};
+use calloop::{EventLoop, LoopHandle, LoopSignal};
+use calloop_wayland_source::WaylandSource;
+use color_eyre::eyre::{eyre, WrapErr};
+use log::{debug, error, info, warn};
 use mpris::PlaybackStatus;
 use mpris::PlayerFinder;
-use mpris::{Event as MprisEvent, FindingError, Player};
+use mpris::{Event as MprisEvent, EventError, FindingError, Player};
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
 use wayland_client::{
     protocol::{
         __interfaces::WL_COMPOSITOR_INTERFACE,
@@ -19,124 +34,596 @@ use wayland_protocols::wp::idle_inhibit::zv1::client::{
     zwp_idle_inhibitor_v1::{self, ZwpIdleInhibitorV1},
 };
 
+use config::Config;
+use dbus_server::Mode;
+
 /// The typical idle timeout is minutes in length.
-/// With that in mind, keeping the sleep duration long here
-/// will reduce CPU usage while still achieving the desired effect.
-const PLAYER_POLL_SLEEP_DURATION: Duration = Duration::from_secs(5);
+/// With that in mind, polling for newly-appeared players at this interval
+/// keeps CPU usage low while still noticing them promptly.
+const PLAYER_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
 /// Error message returned by the playerctld daemon when
 /// there is no active player.
 const PLAYERCTLD_NO_ACTIVE_PLAYER_MESSAGE: &str = "No player is being controlled by playerctld";
 
-fn main() {
-    let conn = Connection::connect_to_env().expect("could not connect to Wayland server");
+/// Starting and maximum delay for the exponential backoff applied between
+/// reconnection attempts after a session fails, e.g. because the Wayland
+/// connection is lost or the D-Bus control interface cannot be registered.
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Signals that the compositor doesn't advertise a global this daemon
+/// needs, which is a permanent fact about the compositor rather than a
+/// transient hiccup. Returned from `run_session` wrapped in a
+/// `color_eyre::Report` so `main` can recognize it by downcasting and stop
+/// instead of reconnecting forever.
+#[derive(Debug)]
+struct MissingGlobalError(String);
+
+impl fmt::Display for MissingGlobalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for MissingGlobalError {}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    init_logging()?;
+
+    let config_path = Config::default_path();
+    // Shared across reconnects so an override set via `SetMode` survives a
+    // transient Wayland or D-Bus hiccup.
+    let dbus_shared = Arc::new(dbus_server::Shared::default());
+
+    let mut reconnect_delay = INITIAL_RECONNECT_DELAY;
+    loop {
+        match run_session(&config_path, Arc::clone(&dbus_shared)) {
+            Ok(()) => {
+                info!("session ended cleanly, restarting");
+                reconnect_delay = INITIAL_RECONNECT_DELAY;
+            }
+            Err(e) if e.downcast_ref::<MissingGlobalError>().is_some() => {
+                error!("{:?}", e);
+                return Err(e);
+            }
+            Err(e) => {
+                error!("session failed, reconnecting in {:?}: {:?}", reconnect_delay, e);
+                thread::sleep(reconnect_delay);
+                reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// Sets up leveled logging to stdout via `log4rs`, in place of raw
+/// `println!`/`eprintln!`.
+fn init_logging() -> color_eyre::Result<()> {
+    use log4rs::append::console::ConsoleAppender;
+    use log4rs::config::{Appender, Config as LogConfig, Root};
+    use log4rs::encode::pattern::PatternEncoder;
+
+    let stdout = ConsoleAppender::builder()
+        .encoder(Box::new(PatternEncoder::new(
+            "{d(%Y-%m-%dT%H:%M:%S%.3f)} {l:<5} {m}{n}",
+        )))
+        .build();
+    let log_config = LogConfig::builder()
+        .appender(Appender::builder().build("stdout", Box::new(stdout)))
+        .build(Root::builder().appender("stdout").build(log::LevelFilter::Info))?;
+    log4rs::init_config(log_config)?;
+    Ok(())
+}
+
+/// Connects to the Wayland server and registers the D-Bus control
+/// interface, runs the calloop event loop until the Wayland connection is
+/// lost, and returns. Called in a loop from `main` with exponential backoff
+/// so the daemon survives compositor restarts instead of exiting. MPRIS
+/// lookups that fail are retried on the next player poll. Losing the
+/// session bus connection that serves the control interface does not end
+/// the session, and the interface is not registered again until the next
+/// session starts.
+fn run_session(config_path: &Path, dbus_shared: Arc<dbus_server::Shared>) -> color_eyre::Result<()> {
+    let config = Config::load(config_path).unwrap_or_else(|e| {
+        warn!("{}, using default config", e);
+        Config::default()
+    });
+
+    let conn = Connection::connect_to_env().wrap_err("could not connect to Wayland server")?;
     let mut event_queue = conn.new_event_queue();
     let qh = event_queue.handle();
     let display = conn.display();
+    let _registry = display.get_registry(&qh, ());
 
-    let _registry = display.get_registry(&qh, ()).unwrap();
+    let dbus_connection = dbus_server::spawn(Arc::clone(&dbus_shared))
+        .wrap_err("could not register D-Bus control interface")?;
 
-    let mut state = State::default();
-    event_queue.blocking_dispatch(&mut state).unwrap();
-    let mut idle_inhibitor = None;
-    loop {
-        let player_finder = PlayerFinder::new().expect("could not connect to DBus");
-        let active_player_opt =
-            find_active_player(&player_finder).expect("error while finding active players");
-
-        if let Some(active_player) = active_player_opt {
-            idle_inhibitor = idle_inhibitor.or_else(|| {
-                let inhibitor = state
-                    .idle_inhibit_manager
-                    .as_ref()
-                    .expect("idle manager should be present")
-                    .create_inhibitor(
-                        state
-                            .surf
-                            .as_ref()
-                            .expect("wayland surface should be present"),
-                        &qh,
-                        (),
-                    )
-                    .expect("could not inhibit idle");
-                conn.roundtrip()
-                    .expect("failed to request creating idle inhibitor");
-                Some(inhibitor)
-            });
-            println!("Idle inhibited by {}", active_player.identity());
-            // Blocks until new events are received.
-            // Guaranteed to (eventually) receive a shutdown event which will break this loop.
-            loop {
-                let events = active_player
-                    .events()
-                    .expect("couldn't watch for player events");
-
-                let mut event_iterator = events.map(|event| {
-                    event.map(|event| {
-                        println!("Received event {:?}", event);
-                        matches!(
-                            event,
-                            MprisEvent::PlayerShutDown | MprisEvent::Stopped | MprisEvent::Paused
-                        )
-                    })
-                });
+    let mut event_loop: EventLoop<State> =
+        EventLoop::try_new().wrap_err("could not create calloop event loop")?;
+    let loop_handle = event_loop.handle();
 
-                let should_allow_idle = event_iterator
-                    .find(|res| matches!(res, Ok(true) | Err(_)))
-                    .unwrap_or_else(|| {
-                        println!("No event ending playback returned, allowing idle");
-                        Ok(true)
-                    })
-                    .unwrap_or_else(|err| {
-                        println!("Error while watching player events, allowing idle: {}", err);
-                        true
-                    });
-
-                if should_allow_idle {
-                    break;
-                }
-            }
-        } else if let Some(i) = idle_inhibitor.as_ref() {
-            i.destroy();
-            idle_inhibitor = None;
-            conn.roundtrip()
-                .expect("failed to request destruction of idle inhibitor");
-            println!("Idle allowed");
-        }
-        thread::sleep(PLAYER_POLL_SLEEP_DURATION)
+    let mut state = State {
+        compositor: None,
+        surf: None,
+        idle_inhibit_manager: None,
+        idle_inhibitor: None,
+        players: HashMap::new(),
+        player_identities: HashMap::new(),
+        config,
+        dbus_connection,
+        dbus_shared,
+        loop_signal: event_loop.get_signal(),
+        fatal_error: None,
+    };
+
+    // A new session starts without an inhibitor, whatever the previous
+    // session had, so report that before anything else.
+    if *state.dbus_shared.active.lock().unwrap() {
+        dbus_server::notify_active_changed(&state.dbus_connection, &state.dbus_shared, false);
+    }
+
+    // One round trip up front so the compositor and idle inhibit globals
+    // are bound before we evaluate any players against them.
+    event_queue
+        .roundtrip(&mut state)
+        .wrap_err("initial round trip with the compositor failed")?;
+
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(loop_handle.clone())
+        .map_err(|e| eyre!("could not insert Wayland connection into the event loop: {e}"))?;
+
+    // A compositor that never advertises this global will never grant it on
+    // a later round trip either, so treat it as permanent rather than
+    // retrying with backoff forever.
+    if state.idle_inhibit_manager.is_none() {
+        return Err(MissingGlobalError(
+            "compositor does not advertise zwp_idle_inhibit_manager_v1; cannot inhibit idling"
+                .to_string(),
+        )
+        .into());
+    }
+
+    insert_override_ping(&loop_handle, conn.clone(), qh.clone(), &state.dbus_shared)
+        .wrap_err("could not insert D-Bus override ping into the event loop")?;
+    insert_player_watcher(&loop_handle, conn.clone(), qh.clone());
+    insert_config_reloader(&loop_handle, config_path.to_path_buf(), conn.clone(), qh.clone());
+
+    event_loop
+        .run(None, &mut state, |_| {})
+        .wrap_err("event loop exited unexpectedly")?;
+
+    match state.fatal_error {
+        Some(e) => Err(e),
+        None => Ok(()),
     }
 }
 
-/// Returns the first active player that is found.
-///
-/// Returns [Ok(None)] when there are no active players
-/// and the playerctld daemon returns a D-Bus error.
-fn find_active_player(player_finder: &PlayerFinder) -> Result<Option<Player>, FindingError> {
-    let res = player_finder.find_all().map(|players| {
-        players.into_iter().find(|p| match p.get_playback_status() {
-            Ok(PlaybackStatus::Playing) => true,
-            Ok(_) => false,
+/// Watches for `SIGHUP` on a background thread (the standard
+/// `signal_hook::iterator::Signals` blocking loop) and forwards it into the
+/// event loop as a request to reload `config_path`.
+fn insert_config_reloader(
+    loop_handle: &LoopHandle<'static, State>,
+    config_path: PathBuf,
+    conn: Connection,
+    qh: QueueHandle<State>,
+) {
+    let (reload_tx, reload_rx) = channel::channel::<()>();
+
+    thread::spawn(move || {
+        let mut signals = match Signals::new([SIGHUP]) {
+            Ok(signals) => signals,
             Err(e) => {
-                println!("Could not get playback status for {} {}", p.identity(), e);
-                false
+                error!("could not register SIGHUP handler, config reload disabled: {}", e);
+                return;
+            }
+        };
+        for _ in signals.forever() {
+            if reload_tx.send(()).is_err() {
+                return;
+            }
+        }
+    });
+
+    loop_handle
+        .insert_source(reload_rx, move |event, _, state| {
+            if let channel::Event::Msg(()) = event {
+                match Config::load(&config_path) {
+                    Ok(config) => {
+                        info!("reloaded config from {}", config_path.display());
+                        state.config = config;
+                        state.reapply_config(&conn, &qh);
+                    }
+                    Err(e) => warn!("{}, keeping previous config", e),
+                }
+            }
+        })
+        .expect("could not insert SIGHUP reload channel into the event loop");
+}
+
+/// Wires a `calloop::ping` source into the event loop and hands the sending
+/// half to `dbus_shared`, so `DaemonInterface::inhibit`/`release`/`set_mode`
+/// (running on the D-Bus connection's own thread) can wake the event loop
+/// and have it re-run `sync_inhibitor` as soon as the override changes,
+/// rather than waiting for the next player poll or event.
+fn insert_override_ping(
+    loop_handle: &LoopHandle<'static, State>,
+    conn: Connection,
+    qh: QueueHandle<State>,
+    dbus_shared: &Arc<dbus_server::Shared>,
+) -> color_eyre::Result<()> {
+    let (ping, ping_source) =
+        calloop::ping::make_ping().wrap_err("could not create override ping")?;
+    *dbus_shared.override_ping.lock().unwrap() = Some(ping);
+
+    loop_handle
+        .insert_source(ping_source, move |_, _, state| {
+            state.sync_inhibitor(&conn, &qh);
+        })
+        .map_err(|e| eyre!("could not insert override ping into the event loop: {e}"))?;
+    Ok(())
+}
+
+/// Registers the sources that drive player discovery and idle inhibition:
+/// a timer that periodically re-enumerates the players on the bus, and a
+/// channel fed by one background thread per known player that blocks on
+/// that player's D-Bus event stream so playback changes are noticed as
+/// soon as they happen rather than on the next poll.
+fn insert_player_watcher(loop_handle: &LoopHandle<'static, State>, conn: Connection, qh: QueueHandle<State>) {
+    let (event_tx, event_rx) = channel::channel::<PlayerWatcherMessage>();
+
+    let timer_conn = conn.clone();
+    let timer_qh = qh.clone();
+    loop_handle
+        .insert_source(Timer::immediate(), move |_, _, state| {
+            state.poll_players(&timer_conn, &timer_qh, &event_tx);
+            TimeoutAction::ToDuration(PLAYER_POLL_INTERVAL)
+        })
+        .expect("could not insert player poll timer into the event loop");
+
+    loop_handle
+        .insert_source(event_rx, move |event, _, state| {
+            if let channel::Event::Msg(message) = event {
+                state.handle_watcher_message(message, &conn, &qh);
             }
         })
+        .expect("could not insert MPRIS event channel into the event loop");
+}
+
+/// Spawns a thread that blocks on `player.events()` and forwards each event
+/// to `sender` tagged with the player's bus name, so the event loop can
+/// keep every known player's `PlaybackStatus` up to date concurrently
+/// instead of only watching a single, pre-selected player.
+fn spawn_event_watcher(player: Player, bus_name: String, sender: channel::Sender<PlayerWatcherMessage>) {
+    thread::spawn(move || {
+        let events = match player.events() {
+            Ok(events) => events,
+            Err(err) => {
+                let _ = sender.send(PlayerWatcherMessage {
+                    bus_name,
+                    event: Err(err.into()),
+                });
+                return;
+            }
+        };
+        for event in events {
+            if sender
+                .send(PlayerWatcherMessage {
+                    bus_name: bus_name.clone(),
+                    event,
+                })
+                .is_err()
+            {
+                // Event loop shut down; nothing left to forward to.
+                return;
+            }
+        }
     });
-    match res {
+}
+
+struct PlayerWatcherMessage {
+    bus_name: String,
+    event: Result<MprisEvent, EventError>,
+}
+
+/// Returns every player currently known to the MPRIS bus, regardless of
+/// playback status.
+///
+/// Returns `Ok(vec![])` when there are no players and the playerctld daemon
+/// returns a D-Bus error.
+fn find_all_players(player_finder: &PlayerFinder) -> Result<Vec<Player>, FindingError> {
+    match player_finder.find_all() {
         Err(FindingError::DBusError(mpris::DBusError::TransportError(ref err)))
             if err.message() == Some(PLAYERCTLD_NO_ACTIVE_PLAYER_MESSAGE) =>
         {
-            Ok(None)
+            Ok(Vec::new())
         }
         other => other,
     }
 }
 
-#[derive(Default)]
+/// What we know about a tracked player: its last-reported `PlaybackStatus`
+/// and whether its current track passes `config.require_video`. The two
+/// change independently (switching tracks doesn't change playback status
+/// and vice versa), so both are recomputed separately as events arrive.
+#[derive(Debug, Clone, Copy)]
+struct PlayerState {
+    status: PlaybackStatus,
+    video_ok: bool,
+}
+
 struct State {
     compositor: Option<WlCompositor>,
     surf: Option<WlSurface>,
     idle_inhibit_manager: Option<ZwpIdleInhibitManagerV1>,
+    idle_inhibitor: Option<ZwpIdleInhibitorV1>,
+    /// Every player we've seen, keyed by D-Bus bus name.
+    players: HashMap<String, PlayerState>,
+    /// `identity()` of each tracked player, so watcher messages (which only
+    /// carry a bus name) can still be checked against `config`.
+    player_identities: HashMap<String, String>,
+    /// Allow/deny rules loaded from the config file, reloaded on `SIGHUP`.
+    config: Config,
+    /// Connection serving the `Inhibit`/`Release`/`SetMode`/`Active`
+    /// control interface, used to emit `InhibitorChanged`.
+    dbus_connection: zbus::blocking::Connection,
+    /// Override mode and reported active state shared with the D-Bus
+    /// interface thread.
+    dbus_shared: Arc<dbus_server::Shared>,
+    /// Stops the event loop from within a callback; `run_session` resumes
+    /// afterwards to decide whether to reconnect.
+    loop_signal: LoopSignal,
+    /// Set just before `loop_signal.stop()` when the Wayland connection is
+    /// unusable, so `run_session` can report the failure to `main`'s
+    /// reconnect loop instead of silently treating it as a clean exit.
+    fatal_error: Option<color_eyre::eyre::Report>,
+}
+
+impl State {
+    /// Records `error` and stops the event loop so `run_session` can return
+    /// it, triggering a reconnect with backoff.
+    fn fail(&mut self, error: color_eyre::eyre::Report) {
+        error!("{:?}", error);
+        self.fatal_error.get_or_insert(error);
+        self.loop_signal.stop();
+    }
+
+    /// Re-enumerates players on the bus, starts a watcher thread for any
+    /// that are new, and drops any that have disappeared without sending a
+    /// `PlayerShutDown` event.
+    fn poll_players(
+        &mut self,
+        conn: &Connection,
+        qh: &QueueHandle<Self>,
+        event_tx: &channel::Sender<PlayerWatcherMessage>,
+    ) {
+        let player_finder = match PlayerFinder::new() {
+            Ok(player_finder) => player_finder,
+            Err(e) => {
+                warn!("could not connect to DBus, will retry next poll: {}", e);
+                return;
+            }
+        };
+        let players = match find_all_players(&player_finder) {
+            Ok(players) => players,
+            Err(e) => {
+                warn!("error while finding players, will retry next poll: {}", e);
+                return;
+            }
+        };
+
+        let seen_bus_names: Vec<String> = players
+            .iter()
+            .map(|p| p.bus_name().to_owned())
+            .collect();
+
+        for player in players {
+            let bus_name = player.bus_name().to_owned();
+            if self.players.contains_key(&bus_name) {
+                continue;
+            }
+
+            if !self.config.allows_identity(player.identity()) {
+                debug!("ignoring {} ({}): excluded by config", player.identity(), bus_name);
+                continue;
+            }
+
+            let status = match player.get_playback_status() {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!("could not get playback status for {}: {}", player.identity(), e);
+                    continue;
+                }
+            };
+            let video_ok = self.player_video_ok(&player);
+            info!("tracking {} ({}), status {:?}", player.identity(), bus_name, status);
+            self.player_identities
+                .insert(bus_name.clone(), player.identity().to_owned());
+            self.players
+                .insert(bus_name.clone(), PlayerState { status, video_ok });
+            spawn_event_watcher(player, bus_name, event_tx.clone());
+        }
+
+        self.players
+            .retain(|bus_name, _| seen_bus_names.contains(bus_name));
+        self.player_identities
+            .retain(|bus_name, _| seen_bus_names.contains(bus_name));
+
+        self.sync_inhibitor(conn, qh);
+    }
+
+    /// Whether `player`'s current track passes `config.require_video`.
+    /// Always `true` when the rule isn't enabled.
+    fn player_video_ok(&self, player: &Player) -> bool {
+        if !self.config.require_video {
+            return true;
+        }
+        match player.get_metadata() {
+            Ok(metadata) => self.config.allows_metadata(&metadata),
+            Err(e) => {
+                warn!("could not get metadata for {}, ignoring: {}", player.identity(), e);
+                false
+            }
+        }
+    }
+
+    /// Called right after `config` is replaced by a `SIGHUP` reload.
+    /// Already-tracked players were evaluated against the old config, and
+    /// `poll_players` never revisits a bus name it already knows, so
+    /// without this they'd only catch up on their next `TrackChanged` or
+    /// shutdown. Drop players whose identity is now blocked, re-check
+    /// `require_video` for the rest, and recompute the inhibitor right
+    /// away rather than waiting for the next poll or event.
+    fn reapply_config(&mut self, conn: &Connection, qh: &QueueHandle<Self>) {
+        let blocked_bus_names: Vec<String> = self
+            .player_identities
+            .iter()
+            .filter(|(_, identity)| !self.config.allows_identity(identity))
+            .map(|(bus_name, _)| bus_name.clone())
+            .collect();
+        for bus_name in blocked_bus_names {
+            info!("no longer tracking {}: excluded by reloaded config", bus_name);
+            self.players.remove(&bus_name);
+            self.player_identities.remove(&bus_name);
+        }
+
+        if !self.config.require_video {
+            for player_state in self.players.values_mut() {
+                player_state.video_ok = true;
+            }
+        } else if !self.players.is_empty() {
+            let player_finder = match PlayerFinder::new() {
+                Ok(player_finder) => player_finder,
+                Err(e) => {
+                    warn!("could not connect to DBus to re-evaluate players, will retry next poll: {}", e);
+                    self.sync_inhibitor(conn, qh);
+                    return;
+                }
+            };
+            let players = match find_all_players(&player_finder) {
+                Ok(players) => players,
+                Err(e) => {
+                    warn!("error while re-evaluating players, will retry next poll: {}", e);
+                    self.sync_inhibitor(conn, qh);
+                    return;
+                }
+            };
+            for player in players {
+                let bus_name = player.bus_name().to_owned();
+                if !self.players.contains_key(&bus_name) {
+                    continue;
+                }
+                let video_ok = self.player_video_ok(&player);
+                if let Some(player_state) = self.players.get_mut(&bus_name) {
+                    player_state.video_ok = video_ok;
+                }
+            }
+        }
+
+        self.sync_inhibitor(conn, qh);
+    }
+
+    fn handle_watcher_message(
+        &mut self,
+        message: PlayerWatcherMessage,
+        conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match message.event {
+            Ok(MprisEvent::PlayerShutDown) => {
+                info!("{} shut down", message.bus_name);
+                self.players.remove(&message.bus_name);
+                self.player_identities.remove(&message.bus_name);
+            }
+            Ok(event @ (MprisEvent::Playing | MprisEvent::Paused | MprisEvent::Stopped)) => {
+                let allowed = self
+                    .player_identities
+                    .get(&message.bus_name)
+                    .is_some_and(|identity| self.config.allows_identity(identity));
+                if allowed {
+                    let status = match event {
+                        MprisEvent::Playing => PlaybackStatus::Playing,
+                        MprisEvent::Paused => PlaybackStatus::Paused,
+                        _ => PlaybackStatus::Stopped,
+                    };
+                    if let Some(player_state) = self.players.get_mut(&message.bus_name) {
+                        player_state.status = status;
+                    }
+                }
+            }
+            // A new track may switch between audio and video, so re-check
+            // `require_video` against its metadata rather than keeping
+            // whatever the previous track was evaluated as.
+            Ok(MprisEvent::TrackChanged(metadata)) => {
+                let video_ok = self.config.allows_metadata(&metadata);
+                if let Some(player_state) = self.players.get_mut(&message.bus_name) {
+                    player_state.video_ok = video_ok;
+                }
+            }
+            Ok(event) => {
+                debug!("received event {:?} from {}", event, message.bus_name);
+            }
+            Err(err) => {
+                warn!(
+                    "error while watching {} for events, assuming it stopped: {}",
+                    message.bus_name, err
+                );
+                self.players.remove(&message.bus_name);
+                self.player_identities.remove(&message.bus_name);
+            }
+        }
+
+        self.sync_inhibitor(conn, qh);
+    }
+
+    /// Aggregates `players` into "any player is playing", folds in the
+    /// manual `SetMode`/`Inhibit`/`Release` override, and creates or
+    /// destroys the idle inhibitor on the 0-\>nonzero / nonzero-\>0
+    /// transition, leaving it alone otherwise. A Wayland request or round
+    /// trip failure here means the connection is unusable, so it's treated
+    /// as fatal and the event loop is stopped to trigger a reconnect.
+    fn sync_inhibitor(&mut self, conn: &Connection, qh: &QueueHandle<Self>) {
+        let should_inhibit = match *self.dbus_shared.mode.lock().unwrap() {
+            Mode::ForceOn => true,
+            Mode::ForceOff => false,
+            Mode::Auto => self.players.iter().any(|(bus_name, p)| {
+                p.status == PlaybackStatus::Playing
+                    && p.video_ok
+                    && self
+                        .player_identities
+                        .get(bus_name)
+                        .is_some_and(|identity| self.config.allows_identity(identity))
+            }),
+        };
+
+        if should_inhibit {
+            if self.idle_inhibitor.is_none() {
+                let Some(idle_inhibit_manager) = self.idle_inhibit_manager.as_ref() else {
+                    return self.fail(eyre!("idle inhibit manager global is missing"));
+                };
+                let Some(surf) = self.surf.as_ref() else {
+                    return self.fail(eyre!("Wayland surface is missing"));
+                };
+                let inhibitor = match idle_inhibit_manager.create_inhibitor(surf, qh, ()) {
+                    Ok(inhibitor) => inhibitor,
+                    Err(e) => return self.fail(eyre!("could not create idle inhibitor: {e}")),
+                };
+                if let Err(e) = conn.roundtrip() {
+                    return self.fail(eyre!("failed to request creating idle inhibitor: {e}"));
+                }
+                self.idle_inhibitor = Some(inhibitor);
+                info!("idle inhibited");
+                dbus_server::notify_active_changed(&self.dbus_connection, &self.dbus_shared, true);
+            }
+        } else if let Some(inhibitor) = self.idle_inhibitor.take() {
+            inhibitor.destroy();
+            if let Err(e) = conn.roundtrip() {
+                return self.fail(eyre!("failed to request destruction of idle inhibitor: {e}"));
+            }
+            info!("idle allowed");
+            dbus_server::notify_active_changed(&self.dbus_connection, &self.dbus_shared, false);
+        }
+    }
 }
 
 impl Dispatch<WlRegistry, ()> for State {
@@ -154,23 +641,30 @@ impl Dispatch<WlRegistry, ()> for State {
                 interface,
                 version,
             } if interface == WL_COMPOSITOR_INTERFACE.name => {
-                let compositor = registry
-                    .bind::<WlCompositor, _, _>(name, version, qh, ())
-                    .unwrap();
-                self.surf = Some(compositor.create_surface(qh, ()).unwrap());
-                self.compositor = Some(compositor);
-                eprintln!("[{}] {} (v{})", name, interface, version);
+                match registry.bind::<WlCompositor, _, _>(name, version, qh, ()) {
+                    Ok(compositor) => {
+                        match compositor.create_surface(qh, ()) {
+                            Ok(surf) => self.surf = Some(surf),
+                            Err(e) => warn!("could not create Wayland surface: {}", e),
+                        }
+                        self.compositor = Some(compositor);
+                        debug!("[{}] {} (v{})", name, interface, version);
+                    }
+                    Err(e) => warn!("could not bind {}: {}", interface, e),
+                }
             }
             wl_registry::Event::Global {
                 name,
                 interface,
                 version,
             } if interface == ZWP_IDLE_INHIBIT_MANAGER_V1_INTERFACE.name => {
-                let idle_inhibit_manager = registry
-                    .bind::<ZwpIdleInhibitManagerV1, _, _>(name, version, qh, ())
-                    .unwrap();
-                self.idle_inhibit_manager = Some(idle_inhibit_manager);
-                eprintln!("[{}] {} (v{})", name, interface, version);
+                match registry.bind::<ZwpIdleInhibitManagerV1, _, _>(name, version, qh, ()) {
+                    Ok(idle_inhibit_manager) => {
+                        self.idle_inhibit_manager = Some(idle_inhibit_manager);
+                        debug!("[{}] {} (v{})", name, interface, version);
+                    }
+                    Err(e) => warn!("could not bind {}: {}", interface, e),
+                }
             }
             // Don't care
             _ => {}